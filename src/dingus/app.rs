@@ -8,8 +8,76 @@ use std::{
 
 use crate::dingus::error::Error;
 use ansi_term::{Color::Green, Style};
+use serde::Deserialize;
+
+type VariableMap = HashMap<String, VariableValue>;
+type AliasMap = HashMap<String, String>;
+
+const MAX_IMPORT_DEPTH: u32 = 5;
+
+#[cfg(windows)]
+const PATH_LIST_SEPARATOR: &str = ";";
+#[cfg(not(windows))]
+const PATH_LIST_SEPARATOR: &str = ":";
+
+// A `.dingus` variable is either a plain scalar or a YAML sequence, the
+// latter used for `PATH`-style values that should be joined together rather
+// than clobbering one another.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum VariableValue {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+type TaskMap = HashMap<String, TaskValue>;
+
+// A `tasks:` entry is either a single command or a list of steps run in
+// sequence, stopping at the first that fails.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TaskValue {
+    Single(String),
+    Multiple(Vec<String>),
+}
 
-type VariableMap = HashMap<String, String>;
+impl TaskValue {
+    fn steps(self) -> Vec<String> {
+        match self {
+            TaskValue::Single(command) => vec![command],
+            TaskValue::Multiple(commands) => commands,
+        }
+    }
+}
+
+// The on-disk shape of a `.dingus` file. `import`, `env`, `aliases` and `tasks` are
+// pulled out explicitly so they never leak into the resulting environment;
+// anything left over is collected by `flatten` so a bare top-level map is
+// still treated purely as environment variables, just like before `env:`
+// existed.
+#[derive(Debug, Default, Deserialize)]
+struct RawDingusFile {
+    #[serde(default)]
+    import: Vec<String>,
+    #[serde(default)]
+    env: VariableMap,
+    #[serde(default)]
+    aliases: AliasMap,
+    #[serde(default)]
+    tasks: TaskMap,
+    #[serde(flatten)]
+    variables: VariableMap,
+}
+
+// The fully resolved contents of a `.dingus` file (and anything it
+// imports): the environment to load, the aliases to install alongside it and
+// the tasks that `run` can execute.
+#[derive(Debug, Default, Clone)]
+struct ResolvedConfig {
+    env: VariableMap,
+    aliases: AliasMap,
+    tasks: TaskMap,
+}
 
 pub trait Application<A, E> {
     type A;
@@ -24,6 +92,7 @@ enum SubCommand {
     Print,
     Session,
     List,
+    Run(String),
 }
 
 #[derive(Debug)]
@@ -60,6 +129,14 @@ impl Application<Dingus, Error> for Dingus {
             ("print", Some(subcommand_matches)) => (SubCommand::Print, subcommand_matches),
             ("session", Some(subcommand_matches)) => (SubCommand::Session, subcommand_matches),
             ("list", Some(subcommand_matches)) => (SubCommand::List, subcommand_matches),
+            ("run", Some(subcommand_matches)) => {
+                let task_name = subcommand_matches
+                    .value_of("task")
+                    .map(str::to_string)
+                    .ok_or(Error::NoSubcommandMatch)?;
+
+                (SubCommand::Run(task_name), subcommand_matches)
+            }
             _ => return Err(Error::NoSubcommandMatch),
         };
 
@@ -117,21 +194,60 @@ impl Application<Dingus, Error> for Dingus {
             SubCommand::Session => self.session(),
             SubCommand::Print => self.print(),
             SubCommand::List => self.list(),
+            SubCommand::Run(_) => self.run_task(),
         }
     }
 }
 
 impl Dingus {
-    fn parse_dingus_file(path: &PathBuf) -> Result<VariableMap, Error> {
+    fn parse_dingus_file(path: &PathBuf) -> Result<ResolvedConfig, Error> {
+        Dingus::parse_dingus_file_at_depth(path, 0)
+    }
+
+    // Parses a `.dingus` file and recursively merges in any files listed
+    // under its `import:` key. Imported values are merged first so the
+    // importing file's own keys win, and `depth` guards against cycles or
+    // runaway nesting between files that import each other.
+    fn parse_dingus_file_at_depth(path: &PathBuf, depth: u32) -> Result<ResolvedConfig, Error> {
+        if depth > MAX_IMPORT_DEPTH {
+            return Err(Error::ImportRecursionLimit {
+                limit: MAX_IMPORT_DEPTH,
+            });
+        }
+
         use std::io::Read;
 
-        let mut config_file = File::open(path)?;
         let mut file_contents = String::new();
-        config_file.read_to_string(&mut file_contents)?;
+        File::open(path)
+            .and_then(|mut config_file| config_file.read_to_string(&mut file_contents))
+            .map_err(|source| Error::ConfigReadError {
+                path: path.clone(),
+                source,
+            })?;
+
+        let raw: RawDingusFile =
+            serde_yaml::from_str(&file_contents).map_err(|source| Error::ConfigParseError {
+                path: path.clone(),
+                source,
+            })?;
+
+        let base_dir = path.parent().map(PathBuf::from).unwrap_or_default();
+        let mut resolved = ResolvedConfig::default();
+
+        for import_path in &raw.import {
+            let imported =
+                Dingus::parse_dingus_file_at_depth(&base_dir.join(import_path), depth + 1)?;
+            resolved.env.extend(imported.env);
+            resolved.aliases.extend(imported.aliases);
+            resolved.tasks.extend(imported.tasks);
+        }
 
-        let variables: VariableMap = serde_yaml::from_str(&file_contents)?;
+        resolved.env.extend(raw.variables);
+        resolved.env.extend(raw.env);
+        resolved.aliases.extend(raw.aliases);
+        resolved.tasks.extend(raw.tasks);
 
-        Ok(variables)
+        Ok(resolved)
     }
 
     fn resolve_config_file(mut path: PathBuf, filename: &str) -> Result<Option<PathBuf>, Error> {
@@ -172,7 +288,34 @@ impl Dingus {
             Err(_) => DEFAULT_LEVEL,
         };
 
-        variable_list.insert(ENV_VAR.to_owned(), level.to_string());
+        variable_list.insert(ENV_VAR.to_owned(), VariableValue::Scalar(level.to_string()));
+    }
+
+    // Resolves a single variable to the string that should actually be set
+    // in the shell: scalars pass through untouched, while lists are joined
+    // with the platform's path separator. Lists are resolved against the
+    // variable's current value in the real environment so they extend it
+    // (`PATH: ["/opt/bin"]` is prepended to the existing `$PATH`) instead of
+    // clobbering it.
+    fn resolve_variable(key: &str, value: &VariableValue) -> String {
+        match value {
+            VariableValue::Scalar(value) => value.clone(),
+            VariableValue::List(items) => {
+                let mut parts = items.clone();
+
+                if let Ok(existing) = env::var(key) {
+                    parts.push(existing);
+                }
+
+                parts.join(PATH_LIST_SEPARATOR)
+            }
+        }
+    }
+
+    fn resolve_env(env: &VariableMap) -> HashMap<String, String> {
+        env.iter()
+            .map(|(key, value)| (key.clone(), Dingus::resolve_variable(key, value)))
+            .collect()
     }
 
     fn recursively_walk_upwards_for_dingus_file(here: PathBuf) -> Option<PathBuf> {
@@ -187,18 +330,97 @@ impl Dingus {
         }
     }
 
+    // Walks upwards from `here` to the filesystem root, collecting every
+    // `.dingus` file found along the way. The result is ordered from the
+    // filesystem root down to `here`, so folding it left-to-right lets
+    // nearer files override values from their ancestors.
+    fn collect_dingus_files_upwards(here: PathBuf) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        let mut current = here;
+
+        loop {
+            let mut possible_location = current.clone();
+            possible_location.push(".dingus");
+
+            if possible_location.exists() {
+                found.push(possible_location);
+            }
+
+            match current.parent() {
+                Some(parent) => current = parent.to_path_buf(),
+                None => break,
+            }
+        }
+
+        found.reverse();
+        found
+    }
+
     // If we have a given config file, parse it. Otherwise walk upwards
-    // towards the root of the filesystem looking for a file named `.dingus`.
-    fn get_environment(&self) -> Result<VariableMap, Error> {
-        let file_to_parse: PathBuf = match self.given_config_file {
-            Some(ref path) => path.clone(),
-            None => Dingus::recursively_walk_upwards_for_dingus_file(env::current_dir()?)
-                .ok_or(Error::DingusFileNotFound)?,
+    // towards the root of the filesystem, merging every `.dingus` file found
+    // along the way so that the file closest to the current directory
+    // overrides values from its ancestors.
+    fn get_environment(&self) -> Result<ResolvedConfig, Error> {
+        let mut config = match self.given_config_file {
+            Some(ref path) => Dingus::parse_dingus_file(path)?,
+            None => {
+                let dingus_files = Dingus::collect_dingus_files_upwards(env::current_dir()?);
+
+                if dingus_files.is_empty() {
+                    return Err(Error::DingusFileNotFound);
+                }
+
+                Dingus::merge_dingus_files(&dingus_files)?
+            }
         };
 
-        let mut environment = Dingus::parse_dingus_file(&file_to_parse)?;
-        Dingus::set_dingus_level(&mut environment);
-        Ok(environment)
+        Dingus::set_dingus_level(&mut config.env);
+        Ok(config)
+    }
+
+    // Parses and merges `files` in order, so that a later file's keys
+    // override an earlier file's. Used to fold a list of `.dingus` files
+    // ordered from the filesystem root down to the current directory, so
+    // the nearer file wins.
+    fn merge_dingus_files(files: &[PathBuf]) -> Result<ResolvedConfig, Error> {
+        files
+            .iter()
+            .try_fold(ResolvedConfig::default(), |mut merged, path| {
+                let layer = Dingus::parse_dingus_file(path)?;
+                merged.env.extend(layer.env);
+                merged.aliases.extend(layer.aliases);
+                merged.tasks.extend(layer.tasks);
+                Ok(merged)
+            })
+    }
+
+    // Renders `aliases` as shell commands in the syntax of `shell`: a plain
+    // `alias` for bash-like shells, and a `function` block for fish (whose
+    // `alias` is itself just sugar for one, but we speak its native form).
+    fn alias_script(shell: &Shell, aliases: &AliasMap) -> String {
+        aliases
+            .iter()
+            .map(|(name, command)| match shell {
+                Shell::Fish(_) => format!(
+                    "function {name}; {command} $argv; end; ",
+                    name = name,
+                    command = command
+                ),
+                Shell::BashLike(_) => format!(
+                    "alias {name}={command}; ",
+                    name = name,
+                    command = Dingus::single_quote(command)
+                ),
+            })
+            .collect()
+    }
+
+    // Wraps `value` in single quotes for POSIX-ish shells, escaping any
+    // single quotes it contains with the standard close-quote,
+    // escaped-quote, reopen-quote trick so the result is always a single
+    // safe shell word.
+    fn single_quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', r#"'\''"#))
     }
 
     fn session(self) -> Result<(), Error> {
@@ -219,10 +441,47 @@ impl Dingus {
             }
         }
 
-        Command::new(self.shell.command())
-            .envs(self.get_environment()?)
+        let config = self.get_environment()?;
+        let mut command = Command::new(self.shell.command());
+        command.envs(Dingus::resolve_env(&config.env));
+
+        if !config.aliases.is_empty() {
+            let alias_script = Dingus::alias_script(&self.shell, &config.aliases);
+
+            match self.shell {
+                Shell::Fish(_) => {
+                    command.arg("--init-command").arg(alias_script);
+                }
+                // `BashLike` covers every non-fish $SHELL, but `--rcfile` is
+                // a bash-only flag -- handing it to zsh, dash, ksh, etc.
+                // makes them bail out immediately with "unknown option".
+                // Only inject aliases for the shells we know how to talk to.
+                Shell::BashLike(ref bin) => match bin.as_str() {
+                    "bash" => {
+                        command
+                            .arg("--rcfile")
+                            .arg(Dingus::write_alias_rcfile(&alias_script)?);
+                    }
+                    "zsh" => {
+                        command.env("ZDOTDIR", Dingus::write_zsh_alias_dotdir(&alias_script)?);
+                    }
+                    other => eprintln!(
+                        "{}",
+                        Style::new().bold().paint(format!(
+                            "Dingus doesn't know how to install aliases into \"{}\" -- skipping alias injection for this session",
+                            other
+                        ))
+                    ),
+                },
+            }
+        }
+
+        command
             .status()
-            .map_err(Error::BadShellVar)?;
+            .map_err(|source| Error::ShellSpawnFailed {
+                shell: self.shell.command().to_string(),
+                source,
+            })?;
 
         Ok(println!(
             "{}",
@@ -230,13 +489,44 @@ impl Dingus {
         ))
     }
 
+    // bash has no `--init-command` flag, so aliases are handed to it via a
+    // throwaway rcfile instead.
+    fn write_alias_rcfile(alias_script: &str) -> Result<PathBuf, Error> {
+        let mut rc_path = env::temp_dir();
+        rc_path.push(format!("dingus-aliases-{}.sh", std::process::id()));
+        fs::write(&rc_path, alias_script)?;
+        Ok(rc_path)
+    }
+
+    // zsh has no `--rcfile` flag either, but it does honor `$ZDOTDIR` for
+    // where to find its startup files. Point it at a throwaway directory
+    // whose `.zshrc` sources the user's real one before adding our aliases,
+    // so interactive zsh sessions pick them up without losing their usual
+    // setup.
+    fn write_zsh_alias_dotdir(alias_script: &str) -> Result<PathBuf, Error> {
+        let mut dotdir = env::temp_dir();
+        dotdir.push(format!("dingus-zdotdir-{}", std::process::id()));
+        fs::create_dir_all(&dotdir)?;
+
+        let mut zshrc_path = dotdir.clone();
+        zshrc_path.push(".zshrc");
+
+        let zshrc_contents = format!(
+            "[ -f \"$HOME/.zshrc\" ] && source \"$HOME/.zshrc\"\n{}\n",
+            alias_script
+        );
+        fs::write(&zshrc_path, zshrc_contents)?;
+
+        Ok(dotdir)
+    }
+
     fn print(self) -> Result<(), Error> {
         use std::io::{self, Write};
 
-        let environment = self.get_environment()?;
-        let mut set_commands: Vec<String> = Vec::with_capacity(environment.len());
+        let config = self.get_environment()?;
+        let mut set_commands: Vec<String> = Vec::with_capacity(config.env.len() + 1);
 
-        for (key, value) in environment {
+        for (key, value) in Dingus::resolve_env(&config.env) {
             match self.shell {
                 Shell::Fish(_) => set_commands.push(
                     format_args!("set -gx {key} \"{value}\"; ", key = key, value = value)
@@ -249,12 +539,70 @@ impl Dingus {
             }
         }
 
+        if !config.aliases.is_empty() {
+            set_commands.push(Dingus::alias_script(&self.shell, &config.aliases));
+        }
+
         let stdout = io::stdout();
         let mut handle = stdout.lock();
         handle.write_all(set_commands.join(" ").as_bytes()).unwrap();
         Ok(())
     }
 
+    // Runs the named task's steps, in order, inside the same environment
+    // `session` would build. Stops at the first step that exits non-zero.
+    fn run_task(self) -> Result<(), Error> {
+        let task_name = match self.subcommand {
+            SubCommand::Run(ref name) => name.clone(),
+            _ => unreachable!("run_task called for a non-Run subcommand"),
+        };
+
+        let config = self.get_environment()?;
+        let env = Dingus::resolve_env(&config.env);
+
+        let task = config
+            .tasks
+            .get(&task_name)
+            .cloned()
+            .ok_or_else(|| Error::NoSuchTask {
+                name: task_name.clone(),
+            })?;
+
+        Dingus::run_steps(&self.shell, &env, &task_name, task.steps())
+    }
+
+    // Runs each step in order with `shell`, stopping and surfacing
+    // `Error::TaskFailed` at the first one that exits non-zero.
+    fn run_steps(
+        shell: &Shell,
+        env: &HashMap<String, String>,
+        task_name: &str,
+        steps: Vec<String>,
+    ) -> Result<(), Error> {
+        use std::process::Command;
+
+        for step in steps {
+            let status = Command::new(shell.command())
+                .arg("-c")
+                .arg(&step)
+                .envs(env.clone())
+                .status()
+                .map_err(|source| Error::ShellSpawnFailed {
+                    shell: shell.command().to_string(),
+                    source,
+                })?;
+
+            if !status.success() {
+                return Err(Error::TaskFailed {
+                    name: task_name.to_string(),
+                    status,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     fn list(self) -> Result<(), Error> {
         let mut output = Vec::new();
 
@@ -312,3 +660,133 @@ impl Dingus {
         Ok(println!("{}", output.join("\n")))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_quote_round_trips_through_a_real_shell() {
+        let value = "it's a 'test' value";
+        let quoted = Dingus::single_quote(value);
+
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s' {}", quoted))
+            .output()
+            .expect("sh should be available to run this test");
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), value);
+    }
+
+    #[test]
+    fn alias_script_escapes_single_quotes_for_bash_like_shells() {
+        let mut aliases = AliasMap::new();
+        aliases.insert(
+            "gl".to_string(),
+            "git log --pretty='%h %s'".to_string(),
+        );
+
+        let script = Dingus::alias_script(&Shell::BashLike("bash".to_string()), &aliases);
+
+        assert_eq!(
+            script,
+            format!(
+                "alias gl={}; ",
+                Dingus::single_quote("git log --pretty='%h %s'")
+            )
+        );
+    }
+
+    #[test]
+    fn run_steps_surfaces_a_failing_step_as_task_failed() {
+        let shell = Shell::BashLike("sh".to_string());
+        let env = HashMap::new();
+
+        let result = Dingus::run_steps(&shell, &env, "deploy", vec!["exit 1".to_string()]);
+
+        match result {
+            Err(Error::TaskFailed { name, .. }) => assert_eq!(name, "deploy"),
+            other => panic!("expected Error::TaskFailed, got {:?}", other),
+        }
+    }
+
+    // Creates an empty directory under the system temp dir, unique to this
+    // test run and `label`, for scratch `.dingus`/imported files.
+    fn unique_temp_dir(label: &str) -> PathBuf {
+        let mut dir = env::temp_dir();
+        dir.push(format!("dingus-test-{}-{}", label, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn imported_variables_lose_to_the_importing_files_own_keys() {
+        let dir = unique_temp_dir("import-precedence");
+
+        let mut imported_path = dir.clone();
+        imported_path.push("shared.yaml");
+        fs::write(&imported_path, "FOO: a\n").unwrap();
+
+        let mut main_path = dir.clone();
+        main_path.push(".dingus");
+        fs::write(&main_path, "import: [\"shared.yaml\"]\nFOO: b\n").unwrap();
+
+        let resolved = Dingus::parse_dingus_file(&main_path).unwrap();
+
+        assert_eq!(
+            resolved.env.get("FOO"),
+            Some(&VariableValue::Scalar("b".to_string()))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nearer_dingus_files_override_ancestors() {
+        let dir = unique_temp_dir("upward-layering");
+
+        let mut ancestor_path = dir.clone();
+        ancestor_path.push("ancestor.yaml");
+        fs::write(&ancestor_path, "FOO: a\n").unwrap();
+
+        let mut nearer_path = dir.clone();
+        nearer_path.push("nearer.yaml");
+        fs::write(&nearer_path, "FOO: b\n").unwrap();
+
+        let resolved = Dingus::merge_dingus_files(&[ancestor_path, nearer_path]).unwrap();
+
+        assert_eq!(
+            resolved.env.get("FOO"),
+            Some(&VariableValue::Scalar("b".to_string()))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_variable_prepends_list_entries_before_the_inherited_value() {
+        const KEY: &str = "DINGUS_TEST_RESOLVE_VARIABLE_LIST";
+        env::set_var(KEY, "/existing/bin");
+
+        let value = VariableValue::List(vec!["/opt/bin".to_string(), "/usr/local/bin".to_string()]);
+        let resolved = Dingus::resolve_variable(KEY, &value);
+
+        env::remove_var(KEY);
+
+        assert_eq!(
+            resolved,
+            format!(
+                "/opt/bin{sep}/usr/local/bin{sep}/existing/bin",
+                sep = PATH_LIST_SEPARATOR
+            )
+        );
+    }
+
+    #[test]
+    fn resolve_variable_passes_scalars_through_untouched() {
+        let value = VariableValue::Scalar("plain".to_string());
+        assert_eq!(Dingus::resolve_variable("ANYTHING", &value), "plain");
+    }
+}
@@ -1,4 +1,4 @@
-use std::{env, io, path::PathBuf};
+use std::{env, io, path::PathBuf, process::ExitStatus};
 use failure::Fail;
 
 use serde_yaml::Error as YamlError;
@@ -8,14 +8,17 @@ pub enum Error {
     #[fail(display = "Looks like your $SHELL environment variable isn't set properly")]
     EnvError(env::VarError),
 
-    #[fail(display = "The config file you specified doesn't exist or isn't valid unicode")]
+    #[fail(display = "An unexpected I/O error occurred: {}", _0)]
     IOError(io::Error),
 
-    #[fail(display = "The config file you specified isn't valid YAML")]
-    SerdeYamlError(YamlError),
+    #[fail(display = "failed to read config file {:?}: {}", path, source)]
+    ConfigReadError { path: PathBuf, source: io::Error },
 
-    #[fail(display = "The <SHELL> argument provided to --shell is invalid")]
-    BadShellVar(io::Error),
+    #[fail(display = "config file {:?} isn't valid YAML: {}", path, source)]
+    ConfigParseError { path: PathBuf, source: YamlError },
+
+    #[fail(display = "failed to start shell \"{}\": {}", shell, source)]
+    ShellSpawnFailed { shell: String, source: io::Error },
 
     #[fail(display = "Invalid [SUBCOMMAND] specified")]
     NoSubcommandMatch,
@@ -34,6 +37,18 @@ pub enum Error {
         two
     )]
     ConflictingConfigPaths { one: PathBuf, two: PathBuf },
+
+    #[fail(
+        display = "Exceeded the maximum `import:` nesting depth of {} -- check for a cycle",
+        limit
+    )]
+    ImportRecursionLimit { limit: u32 },
+
+    #[fail(display = "No task named \"{}\" is defined in the `tasks:` section", name)]
+    NoSuchTask { name: String },
+
+    #[fail(display = "task \"{}\" failed ({})", name, status)]
+    TaskFailed { name: String, status: ExitStatus },
 }
 
 impl From<env::VarError> for Error {
@@ -47,9 +62,3 @@ impl From<io::Error> for Error {
         Error::IOError(err)
     }
 }
-
-impl From<YamlError> for Error {
-    fn from(err: YamlError) -> Error {
-        Error::SerdeYamlError(err)
-    }
-}